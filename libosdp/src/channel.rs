@@ -0,0 +1,216 @@
+//
+// Copyright (c) 2023-2024 Siddharth Chandrasekaran <sidcha.dev@gmail.com>
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! OSDP communicates with its peer over a physical channel (typically a
+//! UART/RS-485 link). This module defines the [Channel] trait that abstracts
+//! that link so the rest of the crate never has to know whether it's talking
+//! to a `std::io` backed serial port or a bare-metal peripheral register.
+
+#[cfg(feature = "std")]
+use std::fmt;
+
+/// Error type returned by [Channel] operations.
+///
+/// On `std` targets this wraps the underlying `std::io::Error`; on `no_std`
+/// targets (enabled via the `alloc`-free `embedded-io` backend) it wraps
+/// `embedded_io::ErrorKind` since `std::io::Error` is unavailable.
+#[derive(Debug)]
+pub enum ChannelError {
+    /// I/O error on `std` targets.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+
+    /// I/O error on `no_std` targets, reported by `embedded-io`.
+    #[cfg(not(feature = "std"))]
+    Io(embedded_io::ErrorKind),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelError::Io(e) => write!(f, "channel I/O error: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChannelError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for ChannelError {
+    fn from(e: std::io::Error) -> Self {
+        ChannelError::Io(e)
+    }
+}
+
+/// A communication channel between a CP and a PD.
+///
+/// Implementors provide the byte-level transport (serial port, TCP socket,
+/// bare-metal UART, ...); OSDP takes care of framing, security and command
+/// semantics on top of it.
+///
+/// On `std` targets a boxed trait object (`Box<dyn Channel>`) is handed to
+/// [crate::PeripheralDevice]/[crate::ControlPanel] directly. On `no_std`
+/// targets, where there is no allocator to box into, any type that
+/// implements `embedded_io::Read + embedded_io::Write` gets a blanket
+/// [Channel] implementation instead (see the `no_std` section below), so it
+/// can be used without going through a trait object at all.
+pub trait Channel {
+    /// Returns an ID that uniquely identifies this channel. When multiple
+    /// PDs share a physical bus, they must report the same ID so OSDP can
+    /// detect that they also share the same underlying medium.
+    fn get_id(&self) -> i32;
+
+    /// Read bytes into `buf`, returning the number of bytes read.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ChannelError>;
+
+    /// Write bytes from `buf`, returning the number of bytes written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, ChannelError>;
+
+    /// Flush any buffered output so it is handed off to the medium.
+    fn flush(&mut self) -> Result<(), ChannelError>;
+}
+
+#[cfg(not(feature = "std"))]
+mod no_std_impl {
+    use super::{Channel, ChannelError};
+    use embedded_io::{Read, Write};
+
+    /// Blanket [Channel] implementation for any `no_std` peripheral that
+    /// already speaks `embedded-io`.
+    ///
+    /// `get_id` always returns `0` since bare-metal targets typically wire a
+    /// single PD to a single dedicated peripheral and have no need to
+    /// disambiguate a shared bus.
+    impl<T> Channel for T
+    where
+        T: Read + Write,
+    {
+        fn get_id(&self) -> i32 {
+            0
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, ChannelError> {
+            Read::read(self, buf).map_err(|e| ChannelError::Io(e.kind()))
+        }
+
+        fn write(&mut self, buf: &[u8]) -> Result<usize, ChannelError> {
+            Write::write(self, buf).map_err(|e| ChannelError::Io(e.kind()))
+        }
+
+        fn flush(&mut self) -> Result<(), ChannelError> {
+            Write::flush(self).map_err(|e| ChannelError::Io(e.kind()))
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod static_ctx {
+    use spin::Once;
+
+    /// `no_std` targets have no `lazy_static`-backed heap allocator to stash
+    /// a device context in, so bare-metal construction routes through this
+    /// statically allocated, `spin`-guarded slot instead: a constructor
+    /// calls [StaticContext::set] once with the context it builds, and every
+    /// later call into the device borrows it back out via
+    /// [StaticContext::get] instead of threading it through as a parameter.
+    ///
+    /// A slot holds one context for the rest of the program; every `set()`
+    /// after the first returns `Err` instead of overwriting it, since there
+    /// is no allocator to give the second caller a distinct instance of its
+    /// own.
+    ///
+    /// ```
+    /// # use libosdp::StaticContext;
+    /// struct PdContext {
+    ///     address: u8,
+    /// }
+    ///
+    /// struct Pd(&'static PdContext);
+    ///
+    /// impl Pd {
+    ///     fn new(slot: &'static StaticContext<PdContext>, address: u8) -> Option<Self> {
+    ///         slot.set(PdContext { address }).ok().map(Pd)
+    ///     }
+    /// }
+    ///
+    /// static PD_CTX: StaticContext<PdContext> = StaticContext::new();
+    /// let pd = Pd::new(&PD_CTX, 101).unwrap();
+    /// assert_eq!(pd.0.address, 101);
+    /// // A second construction attempt against the same slot is rejected,
+    /// // not silently pointed at a second, distinct context.
+    /// assert!(Pd::new(&PD_CTX, 102).is_none());
+    /// ```
+    pub struct StaticContext<T> {
+        inner: Once<T>,
+    }
+
+    impl<T> StaticContext<T> {
+        /// Create an empty, uninitialized static context slot.
+        pub const fn new() -> Self {
+            Self { inner: Once::new() }
+        }
+
+        /// Initialize the slot with `value`, returning a `&'static` reference
+        /// to it.
+        ///
+        /// Returns `Err` with a reference to the value that won the race
+        /// instead if the slot was already initialized -- by an earlier call
+        /// or a concurrent one. A check-then-`call_once` sequence can't tell the
+        /// two apart without racing; tracking which caller actually ran the
+        /// closure here keeps the two cases distinguishable without an extra
+        /// lock.
+        pub fn set(&'static self, value: T) -> Result<&'static T, &'static T> {
+            let mut initialized_here = false;
+            let slot = self.inner.call_once(|| {
+                initialized_here = true;
+                value
+            });
+            if initialized_here {
+                Ok(slot)
+            } else {
+                Err(slot)
+            }
+        }
+
+        /// Borrow the previously initialized value, if any.
+        pub fn get(&'static self) -> Option<&'static T> {
+            self.inner.get()
+        }
+    }
+
+    impl<T> Default for StaticContext<T> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub use static_ctx::StaticContext;
+
+#[cfg(all(test, not(feature = "std")))]
+mod tests {
+    use super::StaticContext;
+
+    #[test]
+    fn set_once_succeeds() {
+        static CTX: StaticContext<u32> = StaticContext::new();
+        assert_eq!(CTX.get(), None);
+        assert_eq!(CTX.set(7), Ok(&7));
+        assert_eq!(CTX.get(), Some(&7));
+    }
+
+    #[test]
+    fn second_set_is_rejected_not_overwritten() {
+        static CTX: StaticContext<u32> = StaticContext::new();
+        assert_eq!(CTX.set(1), Ok(&1));
+        // The second call must neither panic nor silently replace the first
+        // value; it reports the value that actually won.
+        assert_eq!(CTX.set(2), Err(&1));
+        assert_eq!(CTX.get(), Some(&1));
+    }
+}
@@ -0,0 +1,32 @@
+//
+// Copyright (c) 2023-2024 Siddharth Chandrasekaran <sidcha.dev@gmail.com>
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Safe Rust bindings for [libosdp](https://libosdp.sidcha.dev/), an
+//! implementation of IEC 60839-11-5 Open Supervised Device Protocol (OSDP).
+//!
+//! The crate builds against `std` by default. Disable the `std` feature to
+//! target bare-metal peripherals; see [channel] for what changes under
+//! `no_std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+mod channel;
+#[cfg(feature = "std")]
+mod file;
+
+pub use channel::{Channel, ChannelError};
+#[cfg(not(feature = "std"))]
+pub use channel::StaticContext;
+#[cfg(feature = "std")]
+pub use file::*;
+
+/// Errors returned by this crate.
+#[derive(Debug)]
+pub enum OsdpError {
+    /// A file transfer operation failed; the string names the operation that
+    /// failed.
+    #[cfg(feature = "std")]
+    FileTransfer(&'static str),
+}